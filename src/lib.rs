@@ -1,21 +1,51 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
 use std::{mem, ptr};
 
-struct SpscQueue<V: Send + Sync> {
+mod mpmc;
+mod unbounded;
+
+pub use mpmc::{create_mpmc_queue, MpmcQueueConsumer, MpmcQueueProducer};
+pub use unbounded::{create_unbounded_spsc_queue, UnboundedSpscQueueConsumer, UnboundedSpscQueueProducer};
+
+// Padded to a full cache line so the producer's and consumer's indices never share a line and
+// cause false sharing when the other side's core pulls it in on every access.
+#[repr(align(64))]
+struct CacheAligned<T>(T);
+
+// `SINGLE_CORE` selects the ordering used for the head/tail index handoff. On a real multi-core
+// deployment (the default, `false`) the indices need genuine `Acquire`/`Release` fences since the
+// two sides may run reordered on different cores. On a uniprocessor or cooperatively-scheduled
+// single-core deployment there's no cross-core reordering to guard against, so `Relaxed` loads and
+// stores plus a compiler fence (to stop the *compiler* reordering the buffer access around the
+// index) are enough, and measurably cheaper.
+struct SpscQueue<V: Send + Sync, const SINGLE_CORE: bool = false> {
     buffer: *mut V,
     capacity: usize,
     capacity_mask: usize,
     // We implement it at the queue level as it's a common requirement and so that V doesn't have to
-    // be a heavier enum with an end message variant.
-    ended: bool,
-    read_next: usize,
-    write_next: usize,
+    // be a heavier enum with an end message variant. Shared between producer and consumer (via
+    // `Arc`), so it's an `AtomicBool` rather than a plain `bool`: both sides only ever get a shared
+    // reference to this struct.
+    ended: AtomicBool,
+    // Written by the consumer, read by the producer.
+    read_next: CacheAligned<AtomicUsize>,
+    // Written by the producer, read by the consumer.
+    write_next: CacheAligned<AtomicUsize>,
+    // Parking state for the blocking API. Only touched when the spin-based fast path finds the
+    // queue full/empty, so the Mutex is never contended in the common case.
+    producer_thread: Mutex<Option<Thread>>,
+    producer_woken: AtomicBool,
+    consumer_thread: Mutex<Option<Thread>>,
+    consumer_woken: AtomicBool,
 }
 
-unsafe impl<V: Send + Sync> Send for SpscQueue<V> {}
+unsafe impl<V: Send + Sync, const SINGLE_CORE: bool> Send for SpscQueue<V, SINGLE_CORE> {}
 
-unsafe impl<V: Send + Sync> Sync for SpscQueue<V> {}
+unsafe impl<V: Send + Sync, const SINGLE_CORE: bool> Sync for SpscQueue<V, SINGLE_CORE> {}
 
-impl<V: Send + Sync> Drop for SpscQueue<V> {
+impl<V: Send + Sync, const SINGLE_CORE: bool> Drop for SpscQueue<V, SINGLE_CORE> {
     fn drop(&mut self) {
         unsafe {
             let _ = Vec::from_raw_parts(self.buffer, 0, self.capacity);
@@ -23,8 +53,8 @@ impl<V: Send + Sync> Drop for SpscQueue<V> {
     }
 }
 
-impl<V: Send + Sync> SpscQueue<V> {
-    pub fn new(capacity_exponent: usize) -> SpscQueue<V> {
+impl<V: Send + Sync, const SINGLE_CORE: bool> SpscQueue<V, SINGLE_CORE> {
+    pub fn new(capacity_exponent: usize) -> SpscQueue<V, SINGLE_CORE> {
         assert!(capacity_exponent < mem::size_of::<usize>() * 8);
         let capacity = 1 << capacity_exponent;
         let mut vec = Vec::with_capacity(capacity);
@@ -34,40 +64,176 @@ impl<V: Send + Sync> SpscQueue<V> {
             buffer: ptr,
             capacity,
             capacity_mask: capacity - 1,
-            ended: false,
-            read_next: 0,
-            write_next: 0,
+            ended: AtomicBool::new(false),
+            read_next: CacheAligned(AtomicUsize::new(0)),
+            write_next: CacheAligned(AtomicUsize::new(0)),
+            producer_thread: Mutex::new(None),
+            producer_woken: AtomicBool::new(false),
+            consumer_thread: Mutex::new(None),
+            consumer_woken: AtomicBool::new(false),
         }
     }
-}
 
-// Producer owns the underlying queue and drops it when itself is released.
-pub struct SpscQueueProducer<V: Send + Sync> {
-    queue: *mut SpscQueue<V>,
-}
+    // These four helpers are the only places ordering differs between the two flavors; everything
+    // else (buffer indexing, wrap-around, parking) is shared.
+    #[inline(always)]
+    fn load_write_next(&self) -> usize {
+        if SINGLE_CORE {
+            let value = self.write_next.0.load(Ordering::Relaxed);
+            std::sync::atomic::compiler_fence(Ordering::Acquire);
+            value
+        } else {
+            self.write_next.0.load(Ordering::Acquire)
+        }
+    }
 
-impl<V: Send + Sync> Drop for SpscQueueProducer<V> {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = Box::from_raw(self.queue);
-        };
+    #[inline(always)]
+    fn store_write_next(&self, value: usize) {
+        if SINGLE_CORE {
+            std::sync::atomic::compiler_fence(Ordering::Release);
+            self.write_next.0.store(value, Ordering::Relaxed);
+        } else {
+            self.write_next.0.store(value, Ordering::Release);
+        }
+    }
+
+    #[inline(always)]
+    fn load_read_next(&self) -> usize {
+        if SINGLE_CORE {
+            let value = self.read_next.0.load(Ordering::Relaxed);
+            std::sync::atomic::compiler_fence(Ordering::Acquire);
+            value
+        } else {
+            self.read_next.0.load(Ordering::Acquire)
+        }
+    }
+
+    #[inline(always)]
+    fn store_read_next(&self, value: usize) {
+        if SINGLE_CORE {
+            std::sync::atomic::compiler_fence(Ordering::Release);
+            self.read_next.0.store(value, Ordering::Relaxed);
+        } else {
+            self.read_next.0.store(value, Ordering::Release);
+        }
+    }
+
+    // Unparks the consumer if it's parked waiting for data (or the end-of-queue signal).
+    fn wake_consumer(&self) {
+        if !self.consumer_woken.swap(true, Ordering::AcqRel) {
+            if let Some(thread) = self.consumer_thread.lock().unwrap().as_ref() {
+                thread.unpark();
+            }
+        }
+    }
+
+    // Unparks the producer if it's parked waiting for the consumer to free up capacity.
+    fn wake_producer(&self) {
+        if !self.producer_woken.swap(true, Ordering::AcqRel) {
+            if let Some(thread) = self.producer_thread.lock().unwrap().as_ref() {
+                thread.unpark();
+            }
+        }
     }
 }
 
-impl<V: Send + Sync> SpscQueueProducer<V> {
+// Producer and consumer share ownership of the queue via `Arc`: the blocking API means either
+// side can legitimately outlive the other (e.g. the producer finishes and is dropped while the
+// consumer is still draining on another thread), so whichever side is dropped last is the one
+// that frees it.
+pub struct SpscQueueProducer<V: Send + Sync, const SINGLE_CORE: bool = false> {
+    queue: Arc<SpscQueue<V, SINGLE_CORE>>,
+    // Local, uncontended copy of the consumer's `read_next`. Only refreshed from the atomic when
+    // it looks like we've caught up to it, so the hot path avoids touching a line the consumer
+    // is also writing.
+    read_next_cache: usize,
+    write_next: usize,
+}
+
+impl<V: Send + Sync, const SINGLE_CORE: bool> SpscQueueProducer<V, SINGLE_CORE> {
     pub fn enqueue(&mut self, value: V) -> () {
-        let queue = unsafe { &mut *self.queue };
-        while queue.write_next >= queue.read_next + queue.capacity {
-            // Wait for consumer to catch up.
-        };
-        unsafe { ptr::write(queue.buffer.offset((queue.write_next & queue.capacity_mask) as isize), value) };
-        // Increment after setting buffer element.
-        queue.write_next += 1;
+        let queue = &self.queue;
+        while self.write_next >= self.read_next_cache + queue.capacity {
+            // Our cached view is stale; refresh from the consumer's published index.
+            self.read_next_cache = queue.load_read_next();
+        }
+        unsafe { ptr::write(queue.buffer.offset((self.write_next & queue.capacity_mask) as isize), value) };
+        self.write_next += 1;
+        // Published so the value written above is visible to the consumer once it observes this
+        // store.
+        queue.store_write_next(self.write_next);
+    }
+
+    /// Like [`enqueue`](Self::enqueue), but parks the calling thread instead of spinning while
+    /// the queue is full, trading a little latency for not burning a core. For this to ever be
+    /// woken up once parked, the consumer must drain with
+    /// [`dequeue_blocking`](SpscQueueConsumer::dequeue_blocking), not plain
+    /// [`dequeue`](SpscQueueConsumer::dequeue) — only the former calls `wake_producer`.
+    pub fn enqueue_blocking(&mut self, value: V) -> () {
+        loop {
+            let queue = &self.queue;
+            if self.write_next < self.read_next_cache + queue.capacity {
+                break;
+            }
+            self.read_next_cache = queue.load_read_next();
+            if self.write_next < self.read_next_cache + queue.capacity {
+                break;
+            }
+            self.park_until_writable();
+        }
+        let queue = &self.queue;
+        unsafe { ptr::write(queue.buffer.offset((self.write_next & queue.capacity_mask) as isize), value) };
+        self.write_next += 1;
+        queue.store_write_next(self.write_next);
+        queue.wake_consumer();
+    }
+
+    fn park_until_writable(&mut self) {
+        let queue = &self.queue;
+        *queue.producer_thread.lock().unwrap() = Some(thread::current());
+        queue.producer_woken.store(false, Ordering::Release);
+        // Re-check after registering so we don't miss a wake-up that raced with registration.
+        self.read_next_cache = queue.load_read_next();
+        if self.write_next < self.read_next_cache + queue.capacity {
+            return;
+        }
+        while !queue.producer_woken.load(Ordering::Acquire) {
+            thread::park();
+        }
     }
 
     pub fn finish(&mut self) -> () {
-        let queue = unsafe { &mut *self.queue };
-        queue.ended = true;
+        let queue = &self.queue;
+        queue.ended.store(true, Ordering::Release);
+        // Wake a consumer parked on `dequeue_blocking` so it observes `ended` promptly.
+        queue.wake_consumer();
+    }
+}
+
+impl<V: Send + Sync + Copy, const SINGLE_CORE: bool> SpscQueueProducer<V, SINGLE_CORE> {
+    /// Bulk version of [`enqueue`](Self::enqueue): moves as many elements of `values` as
+    /// currently fit before the ring wraps in one `ptr::copy_nonoverlapping` and one index
+    /// publish, instead of paying an atomic fence and bounds check per element. Returns the
+    /// number of elements actually moved, which may be less than `values.len()` if the queue
+    /// doesn't have room or the run is cut short by the wrap point; call again (or loop) for the
+    /// remainder.
+    pub fn enqueue_slice(&mut self, values: &[V]) -> usize {
+        if values.is_empty() {
+            return 0;
+        }
+        let queue = &self.queue;
+        while self.write_next >= self.read_next_cache + queue.capacity {
+            self.read_next_cache = queue.load_read_next();
+        }
+        let free = (self.read_next_cache + queue.capacity) - self.write_next;
+        let start = self.write_next & queue.capacity_mask;
+        let until_wrap = queue.capacity - start;
+        let n = values.len().min(free).min(until_wrap);
+        unsafe { ptr::copy_nonoverlapping(values.as_ptr(), queue.buffer.offset(start as isize), n) };
+        self.write_next += n;
+        queue.store_write_next(self.write_next);
+        queue.wake_consumer();
+        n
     }
 }
 
@@ -77,41 +243,36 @@ pub enum MaybeDequeued<V> {
     Some(V),
 }
 
-pub struct SpscQueueConsumer<V: Send + Sync> {
-    queue: *mut SpscQueue<V>,
+pub struct SpscQueueConsumer<V: Send + Sync, const SINGLE_CORE: bool = false> {
+    queue: Arc<SpscQueue<V, SINGLE_CORE>>,
+    // Local, uncontended copy of the producer's `write_next`. Only refreshed from the atomic
+    // once we've drained everything we already know about.
+    write_next_cache: usize,
+    read_next: usize,
 }
 
-unsafe impl<V: Send + Sync> Send for SpscQueueConsumer<V> {}
-
-unsafe impl<V: Send + Sync> Sync for SpscQueueConsumer<V> {}
-
-impl<V: Send + Sync> SpscQueueConsumer<V> {
+impl<V: Send + Sync, const SINGLE_CORE: bool> SpscQueueConsumer<V, SINGLE_CORE> {
     #[inline(always)]
-    fn queue(&self) -> &SpscQueue<V> {
-        unsafe { &*self.queue }
-    }
-
-    #[inline(always)]
-    fn queue_mut(&self) -> &mut SpscQueue<V> {
-        unsafe { &mut *self.queue }
-    }
-
-    #[inline(always)]
-    pub fn is_empty(&self) -> bool {
-        let queue = self.queue();
-        queue.read_next >= queue.write_next
+    pub fn is_empty(&mut self) -> bool {
+        if self.read_next >= self.write_next_cache {
+            self.write_next_cache = self.queue.load_write_next();
+        }
+        self.read_next >= self.write_next_cache
     }
 
     pub fn maybe_dequeue(&mut self) -> MaybeDequeued<V> {
         if self.is_empty() {
-            if self.queue().ended {
+            if self.queue.ended.load(Ordering::Acquire) {
                 return MaybeDequeued::Ended;
             };
             return MaybeDequeued::None;
         };
-        let queue = self.queue_mut();
-        let value = unsafe { ptr::read(queue.buffer.offset((queue.read_next & queue.capacity_mask) as isize)) };
-        queue.read_next += 1;
+        let queue = &self.queue;
+        let value = unsafe { ptr::read(queue.buffer.offset((self.read_next & queue.capacity_mask) as isize)) };
+        self.read_next += 1;
+        // Published so the producer's next capacity check (which loads this) sees the slot as
+        // free only after our read above has completed.
+        queue.store_read_next(self.read_next);
         MaybeDequeued::Some(value)
     }
 
@@ -126,9 +287,102 @@ impl<V: Send + Sync> SpscQueueConsumer<V> {
             };
         };
     }
+
+    /// Like [`dequeue`](Self::dequeue), but parks the calling thread instead of spinning while
+    /// the queue is empty, trading a little latency for not burning a core. For this to ever be
+    /// woken up once parked, the producer must publish with
+    /// [`enqueue_blocking`](SpscQueueProducer::enqueue_blocking) or
+    /// [`enqueue_slice`](SpscQueueProducer::enqueue_slice), not plain
+    /// [`enqueue`](SpscQueueProducer::enqueue) — only those call `wake_consumer`.
+    pub fn dequeue_blocking(&mut self) -> Option<V> {
+        loop {
+            match self.maybe_dequeue() {
+                MaybeDequeued::None => self.park_until_readable(),
+                MaybeDequeued::Ended => return None,
+                MaybeDequeued::Some(v) => {
+                    // Only the blocking producer side needs waking; plain dequeue() shouldn't pay
+                    // for the wake check on every call.
+                    self.queue.wake_producer();
+                    return Some(v);
+                }
+            };
+        }
+    }
+
+    fn park_until_readable(&mut self) {
+        let queue = &self.queue;
+        *queue.consumer_thread.lock().unwrap() = Some(thread::current());
+        queue.consumer_woken.store(false, Ordering::Release);
+        // Re-check after registering so we don't miss a wake-up that raced with registration.
+        if !self.is_empty() || self.queue.ended.load(Ordering::Acquire) {
+            return;
+        }
+        let queue = &self.queue;
+        while !queue.consumer_woken.load(Ordering::Acquire) {
+            thread::park();
+        }
+    }
+}
+
+impl<V: Send + Sync + Copy, const SINGLE_CORE: bool> SpscQueueConsumer<V, SINGLE_CORE> {
+    /// Bulk version of [`dequeue`](Self::dequeue): fills as much of `values` as currently
+    /// available before the ring wraps in one `ptr::copy_nonoverlapping` and one index publish,
+    /// instead of paying an atomic fence and bounds check per element. Returns the number of
+    /// elements actually moved, which may be less than `values.len()` if the queue doesn't have
+    /// enough data or the run is cut short by the wrap point; call again (or loop) for the rest.
+    pub fn dequeue_slice(&mut self, values: &mut [V]) -> usize {
+        if values.is_empty() {
+            return 0;
+        }
+        if self.read_next >= self.write_next_cache {
+            self.write_next_cache = self.queue.load_write_next();
+        }
+        let available = self.write_next_cache - self.read_next;
+        let n = {
+            let queue = &self.queue;
+            let start = self.read_next & queue.capacity_mask;
+            let until_wrap = queue.capacity - start;
+            let n = values.len().min(available).min(until_wrap);
+            unsafe { ptr::copy_nonoverlapping(queue.buffer.offset(start as isize), values.as_mut_ptr(), n) };
+            n
+        };
+        self.read_next += n;
+        let queue = &self.queue;
+        queue.store_read_next(self.read_next);
+        queue.wake_producer();
+        n
+    }
 }
 
 pub fn create_spsc_queue<V: Send + Sync>(capacity_exponent: usize) -> (SpscQueueProducer<V>, SpscQueueConsumer<V>) {
-    let queue = Box::into_raw(Box::new(SpscQueue::<V>::new(capacity_exponent)));
-    (SpscQueueProducer { queue }, SpscQueueConsumer { queue })
+    create_spsc_queue_generic::<V, false>(capacity_exponent)
+}
+
+/// Like [`create_spsc_queue`], but for single-core or cooperatively-scheduled deployments (e.g.
+/// an embedded target with no second core, or a WASM single-threaded executor switching between
+/// producer and consumer cooperatively) where there's no real cross-core reordering to guard
+/// against. Trades the `Acquire`/`Release` fences for `Relaxed` loads/stores plus a compiler
+/// fence, which is measurably cheaper on such targets.
+pub fn create_spsc_queue_single_core<V: Send + Sync>(
+    capacity_exponent: usize,
+) -> (SpscQueueProducer<V, true>, SpscQueueConsumer<V, true>) {
+    create_spsc_queue_generic::<V, true>(capacity_exponent)
+}
+
+fn create_spsc_queue_generic<V: Send + Sync, const SINGLE_CORE: bool>(
+    capacity_exponent: usize,
+) -> (SpscQueueProducer<V, SINGLE_CORE>, SpscQueueConsumer<V, SINGLE_CORE>) {
+    let queue = Arc::new(SpscQueue::<V, SINGLE_CORE>::new(capacity_exponent));
+    (
+        SpscQueueProducer {
+            queue: queue.clone(),
+            read_next_cache: 0,
+            write_next: 0,
+        },
+        SpscQueueConsumer {
+            queue,
+            write_next_cache: 0,
+            read_next: 0,
+        },
+    )
 }
@@ -0,0 +1,153 @@
+use std::cell::UnsafeCell;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Vyukov's bounded MPMC array queue: each cell carries its own sequence number, which doubles as
+// both the "is this slot ready for me" check and the synchronisation point between producers and
+// consumers, so no single global lock or CAS loop over the whole queue is needed.
+#[repr(align(64))]
+struct Cell<V> {
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<V>>,
+}
+
+unsafe impl<V: Send> Sync for Cell<V> {}
+
+struct MpmcQueue<V: Send + Sync> {
+    buffer: Box<[Cell<V>]>,
+    capacity_mask: usize,
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+}
+
+unsafe impl<V: Send + Sync> Send for MpmcQueue<V> {}
+
+unsafe impl<V: Send + Sync> Sync for MpmcQueue<V> {}
+
+impl<V: Send + Sync> Drop for MpmcQueue<V> {
+    fn drop(&mut self) {
+        // Only the values still sitting between the two positions are live; drop those, leave
+        // the rest as the uninitialised slots they already are.
+        let mut pos = *self.dequeue_pos.get_mut();
+        let end = *self.enqueue_pos.get_mut();
+        while pos != end {
+            let cell = &mut self.buffer[pos & self.capacity_mask];
+            unsafe { ptr::drop_in_place(cell.value.get_mut().as_mut_ptr()) };
+            pos = pos.wrapping_add(1);
+        }
+    }
+}
+
+impl<V: Send + Sync> MpmcQueue<V> {
+    fn new(capacity_exponent: usize) -> MpmcQueue<V> {
+        assert!(capacity_exponent < mem::size_of::<usize>() * 8);
+        let capacity = 1usize << capacity_exponent;
+        let buffer = (0..capacity)
+            .map(|i| Cell {
+                sequence: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        MpmcQueue {
+            buffer,
+            capacity_mask: capacity - 1,
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+        }
+    }
+
+    fn try_enqueue(&self, value: V) -> Result<(), V> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let cell = &self.buffer[pos & self.capacity_mask];
+            let seq = cell.sequence.load(Ordering::Acquire);
+            let diff = seq as isize - pos as isize;
+            if diff == 0 {
+                // This cell is free and waiting for position `pos`; try to claim it.
+                if self
+                    .enqueue_pos
+                    .compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    unsafe { (*cell.value.get()).write(value) };
+                    // Release so a consumer that observes this sequence also sees the value
+                    // written above.
+                    cell.sequence.store(pos + 1, Ordering::Release);
+                    return Ok(());
+                }
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            } else if diff < 0 {
+                // The slot at `pos` hasn't been freed by a consumer yet: the queue is full.
+                return Err(value);
+            } else {
+                // Another producer already claimed `pos`; retry at the current position.
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn try_dequeue(&self) -> Option<V> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let cell = &self.buffer[pos & self.capacity_mask];
+            let seq = cell.sequence.load(Ordering::Acquire);
+            let diff = seq as isize - (pos as isize + 1);
+            if diff == 0 {
+                // This cell holds the value waiting for position `pos`; try to claim it.
+                if self
+                    .dequeue_pos
+                    .compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    let value = unsafe { (*cell.value.get()).assume_init_read() };
+                    // Release so a producer wrapping back around to this cell sees it as free
+                    // only after our read above has completed.
+                    cell.sequence.store(pos + self.capacity_mask + 1, Ordering::Release);
+                    return Some(value);
+                }
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            } else if diff < 0 {
+                // No producer has published a value for position `pos` yet: the queue is empty.
+                return None;
+            } else {
+                // Another consumer already claimed `pos`; retry at the current position.
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MpmcQueueProducer<V: Send + Sync> {
+    queue: Arc<MpmcQueue<V>>,
+}
+
+impl<V: Send + Sync> MpmcQueueProducer<V> {
+    /// Attempts to enqueue `value`, returning it back in `Err` if the queue is currently full.
+    pub fn try_enqueue(&self, value: V) -> Result<(), V> {
+        self.queue.try_enqueue(value)
+    }
+}
+
+#[derive(Clone)]
+pub struct MpmcQueueConsumer<V: Send + Sync> {
+    queue: Arc<MpmcQueue<V>>,
+}
+
+impl<V: Send + Sync> MpmcQueueConsumer<V> {
+    /// Attempts to dequeue a value, returning `None` if the queue is currently empty.
+    pub fn try_dequeue(&self) -> Option<V> {
+        self.queue.try_dequeue()
+    }
+}
+
+pub fn create_mpmc_queue<V: Send + Sync>(capacity_exponent: usize) -> (MpmcQueueProducer<V>, MpmcQueueConsumer<V>) {
+    let queue = Arc::new(MpmcQueue::new(capacity_exponent));
+    (
+        MpmcQueueProducer { queue: queue.clone() },
+        MpmcQueueConsumer { queue },
+    )
+}
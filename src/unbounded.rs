@@ -0,0 +1,201 @@
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
+
+use crate::MaybeDequeued;
+
+// Classic Michael & Scott style singly linked list: `head` always points at a consumed "dummy"
+// node whose `value` has already been taken (or was never set, for the very first node); the
+// live, not-yet-read values are the ones reachable by following `next` from there. The producer
+// only ever touches `tail` and appends; the consumer only ever touches `head` and advances, so
+// the only cross-thread communication is the `next` pointer linking a node to its successor.
+struct Node<V> {
+    value: MaybeUninit<V>,
+    next: AtomicPtr<Node<V>>,
+}
+
+impl<V> Node<V> {
+    fn new_dummy() -> *mut Node<V> {
+        Box::into_raw(Box::new(Node {
+            value: MaybeUninit::uninit(),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+// Single-producer/single-consumer free list of spent nodes. The consumer pushes a node here once
+// it's advanced past it; the producer pops from here before falling back to allocating, so
+// allocation is bounded by the high-water mark of in-flight items rather than happening on every
+// enqueue.
+struct FreeList<V> {
+    head: AtomicPtr<Node<V>>,
+}
+
+impl<V> FreeList<V> {
+    fn new() -> FreeList<V> {
+        FreeList {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn push(&self, node: *mut Node<V>) {
+        loop {
+            let head = self.head.load(Ordering::Relaxed);
+            unsafe { (*node).next.store(head, Ordering::Relaxed) };
+            if self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<*mut Node<V>> {
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            if head.is_null() {
+                return None;
+            }
+            let next = unsafe { (*head).next.load(Ordering::Relaxed) };
+            if self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Some(head);
+            }
+        }
+    }
+}
+
+struct UnboundedSpscQueue<V: Send + Sync> {
+    free_list: FreeList<V>,
+    // Same "set once, read many times" flag as the bounded queue, atomic for the same reason:
+    // finish() and maybe_dequeue() run on different threads with no other synchronisation
+    // between them.
+    ended: AtomicBool,
+}
+
+impl<V: Send + Sync> Drop for UnboundedSpscQueue<V> {
+    fn drop(&mut self) {
+        // Only the free list is this struct's responsibility; the live chain reachable from the
+        // consumer's `head` is freed by UnboundedSpscQueueConsumer's own Drop impl.
+        while let Some(node) = self.free_list.pop() {
+            let _ = unsafe { Box::from_raw(node) };
+        }
+    }
+}
+
+// Shared via Arc so the queue is only freed once both the producer and consumer are gone,
+// regardless of which side finishes and drops first.
+pub struct UnboundedSpscQueueProducer<V: Send + Sync> {
+    queue: Arc<UnboundedSpscQueue<V>>,
+    tail: *mut Node<V>,
+}
+
+unsafe impl<V: Send + Sync> Send for UnboundedSpscQueueProducer<V> {}
+
+unsafe impl<V: Send + Sync> Sync for UnboundedSpscQueueProducer<V> {}
+
+impl<V: Send + Sync> UnboundedSpscQueueProducer<V> {
+    pub fn enqueue(&mut self, value: V) -> () {
+        let node = self.queue.free_list.pop().unwrap_or_else(Node::new_dummy);
+        unsafe {
+            (*node).value = MaybeUninit::new(value);
+            (*node).next.store(ptr::null_mut(), Ordering::Relaxed);
+            // Release so the value written above is visible to the consumer once it observes
+            // this store.
+            (*self.tail).next.store(node, Ordering::Release);
+        }
+        self.tail = node;
+    }
+
+    pub fn finish(&mut self) -> () {
+        // Release so a consumer that observes this store also sees every enqueue() published
+        // before it.
+        self.queue.ended.store(true, Ordering::Release);
+    }
+}
+
+pub struct UnboundedSpscQueueConsumer<V: Send + Sync> {
+    queue: Arc<UnboundedSpscQueue<V>>,
+    head: *mut Node<V>,
+}
+
+unsafe impl<V: Send + Sync> Send for UnboundedSpscQueueConsumer<V> {}
+
+unsafe impl<V: Send + Sync> Sync for UnboundedSpscQueueConsumer<V> {}
+
+impl<V: Send + Sync> Drop for UnboundedSpscQueueConsumer<V> {
+    fn drop(&mut self) {
+        unsafe {
+            let mut node = self.head;
+            loop {
+                let next = (*node).next.load(Ordering::Relaxed);
+                // `node`'s value has already been taken (or it's the original dummy, which never
+                // had one), so freeing it doesn't need to drop `value`.
+                let _ = Box::from_raw(node);
+                if next.is_null() {
+                    break;
+                }
+                // `next` holds a real value nobody will ever dequeue now; drop it before we loop
+                // around and free its node.
+                ptr::drop_in_place((*next).value.as_mut_ptr());
+                node = next;
+            }
+        };
+    }
+}
+
+impl<V: Send + Sync> UnboundedSpscQueueConsumer<V> {
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        unsafe { (*self.head).next.load(Ordering::Acquire).is_null() }
+    }
+
+    pub fn maybe_dequeue(&mut self) -> MaybeDequeued<V> {
+        let next = unsafe { (*self.head).next.load(Ordering::Acquire) };
+        if next.is_null() {
+            if self.queue.ended.load(Ordering::Acquire) {
+                return MaybeDequeued::Ended;
+            };
+            return MaybeDequeued::None;
+        };
+        let queue = &self.queue;
+        let value = unsafe { (*next).value.as_ptr().read() };
+        let old_head = self.head;
+        self.head = next;
+        queue.free_list.push(old_head);
+        MaybeDequeued::Some(value)
+    }
+
+    pub fn dequeue(&mut self) -> Option<V> {
+        loop {
+            match self.maybe_dequeue() {
+                // Wait for producer to provide values.
+                MaybeDequeued::None => {}
+                // We've caught up to the end.
+                MaybeDequeued::Ended => return None,
+                MaybeDequeued::Some(v) => return Some(v),
+            };
+        };
+    }
+}
+
+pub fn create_unbounded_spsc_queue<V: Send + Sync>() -> (UnboundedSpscQueueProducer<V>, UnboundedSpscQueueConsumer<V>) {
+    let dummy = Node::new_dummy();
+    let queue = Arc::new(UnboundedSpscQueue {
+        free_list: FreeList::new(),
+        ended: AtomicBool::new(false),
+    });
+    (
+        UnboundedSpscQueueProducer {
+            queue: queue.clone(),
+            tail: dummy,
+        },
+        UnboundedSpscQueueConsumer { queue, head: dummy },
+    )
+}